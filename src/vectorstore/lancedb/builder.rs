@@ -1,16 +1,33 @@
-use std::{error::Error, sync::Arc};
+use std::{error::Error, path::PathBuf, sync::Arc};
 
-use super::Store;
-use lancedb::{connect, Connection};
+use super::{
+    chunking::ChunkConfig,
+    lancedb::{IndexConfig, VectorElementType},
+    Store,
+};
+use lancedb::{connect, Connection, DistanceType};
 
 use crate::embedding::embedder_trait::Embedder;
 
+/// Default RRF smoothing constant used by [`Store::similarity_search_hybrid`](super::Store::similarity_search_hybrid).
+const DEFAULT_RRF_K: usize = 60;
+
 pub struct StoreBuilder {
     connection: Option<Connection>,
     connection_url: Option<String>,
     table: String,
     vector_dimensions: i32,
     embedder: Option<Arc<dyn Embedder>>,
+    rrf_k: usize,
+    hybrid_candidate_depth: Option<usize>,
+    filterable_metadata_keys: Vec<String>,
+    embedder_cache_id: String,
+    max_tokens_per_embedding_batch: Option<usize>,
+    embedding_cache_dir: Option<PathBuf>,
+    chunking: Option<ChunkConfig>,
+    element_type: VectorElementType,
+    distance_metric: DistanceType,
+    index_config: IndexConfig,
 }
 
 impl Default for StoreBuilder {
@@ -27,6 +44,16 @@ impl StoreBuilder {
             table: "documents".to_string(),
             vector_dimensions: 0,
             embedder: None,
+            rrf_k: DEFAULT_RRF_K,
+            hybrid_candidate_depth: None,
+            filterable_metadata_keys: Vec::new(),
+            embedder_cache_id: "default".to_string(),
+            max_tokens_per_embedding_batch: None,
+            embedding_cache_dir: None,
+            chunking: None,
+            element_type: VectorElementType::Float32,
+            distance_metric: DistanceType::Cosine,
+            index_config: IndexConfig::Auto,
         }
     }
 
@@ -57,6 +84,95 @@ impl StoreBuilder {
         self
     }
 
+    /// Sets the RRF smoothing constant `k` used by `similarity_search_hybrid`
+    /// to fuse the vector and keyword retrievers. Defaults to 60.
+    pub fn rrf_k(mut self, rrf_k: usize) -> Self {
+        self.rrf_k = rrf_k;
+        self
+    }
+
+    /// Sets how many candidates `similarity_search_hybrid` pulls from each
+    /// retriever before fusion. Defaults to `4 * limit` when unset.
+    pub fn hybrid_candidate_depth(mut self, hybrid_candidate_depth: usize) -> Self {
+        self.hybrid_candidate_depth = Some(hybrid_candidate_depth);
+        self
+    }
+
+    /// Promotes the given `Document.metadata` keys to their own scalar
+    /// columns so `VecStoreOptions` filters can prefilter on them in SQL
+    /// (e.g. `source`, a tenant id). Keys not listed here still round-trip
+    /// through the opaque `metadata` JSON column but cannot be filtered on.
+    pub fn filterable_metadata_keys<S: Into<String>>(mut self, keys: Vec<S>) -> Self {
+        self.filterable_metadata_keys = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Identifies the embedder in the on-disk embedding cache key. Bump
+    /// this (or use a different id) when the embedder's model changes, so
+    /// stale cached vectors from a previous model are never reused.
+    /// Defaults to `"default"`.
+    pub fn embedder_cache_id<S: Into<String>>(mut self, embedder_cache_id: S) -> Self {
+        self.embedder_cache_id = embedder_cache_id.into();
+        self
+    }
+
+    /// Enables the batched embedding queue: `add_documents` will batch
+    /// uncached texts to stay under `max_tokens_per_batch` tokens per
+    /// `embed_documents` call, retrying rate-limited calls with
+    /// exponential backoff. Unset (the default) embeds the whole input
+    /// slice in one call, matching the previous behavior.
+    pub fn max_tokens_per_embedding_batch(mut self, max_tokens_per_batch: usize) -> Self {
+        self.max_tokens_per_embedding_batch = Some(max_tokens_per_batch);
+        self
+    }
+
+    /// Enables the content-addressed embedding cache at `cache_dir`, keyed
+    /// by a hash of `(text, embedder_cache_id)`, so unchanged documents are
+    /// never re-embedded across runs. Only takes effect together with
+    /// `max_tokens_per_embedding_batch`.
+    pub fn embedding_cache_dir(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+        self.embedding_cache_dir = Some(cache_dir.into());
+        self
+    }
+
+    /// Splits each incoming `Document` into overlapping chunks before
+    /// embedding, storing one `VectorRecord` per chunk and carrying the
+    /// parent document id plus the chunk's byte offset range in
+    /// metadata (`parent_id`, `chunk_start`, `chunk_end`). Unset (the
+    /// default) embeds each document whole, matching the previous
+    /// behavior.
+    pub fn chunking(mut self, max_tokens: usize, overlap_tokens: usize) -> Self {
+        self.chunking = Some(ChunkConfig {
+            max_tokens,
+            overlap_tokens,
+        });
+        self
+    }
+
+    /// Sets the element type `text_embedding` is stored as. Defaults to
+    /// `Float32`, halving storage/memory versus `Float64`.
+    pub fn element_type(mut self, element_type: VectorElementType) -> Self {
+        self.element_type = element_type;
+        self
+    }
+
+    /// Sets the distance metric used for query scoring and for an
+    /// `IndexConfig::IvfPq` index. An `IndexConfig::Auto` index is built
+    /// with LanceDB's default metric; this setting is not passed to it.
+    /// Defaults to `Cosine`. With `Cosine`/`Dot`, vectors are
+    /// normalized to unit length at insert and query time so a plain dot
+    /// product yields cosine similarity.
+    pub fn distance_metric(mut self, distance_metric: DistanceType) -> Self {
+        self.distance_metric = distance_metric;
+        self
+    }
+
+    /// Sets the vector index configuration. Defaults to `Auto`.
+    pub fn index_config(mut self, index_config: IndexConfig) -> Self {
+        self.index_config = index_config;
+        self
+    }
+
     // Finalize the builder and construct the Store object
     pub async fn build(self) -> Result<Store, Box<dyn Error>> {
         if self.embedder.is_none() {
@@ -78,6 +194,16 @@ impl StoreBuilder {
             table: self.table,
             vector_dimensions: self.vector_dimensions,
             embedder: self.embedder.unwrap(),
+            rrf_k: self.rrf_k,
+            hybrid_candidate_depth: self.hybrid_candidate_depth,
+            filterable_metadata_keys: self.filterable_metadata_keys,
+            embedder_cache_id: self.embedder_cache_id,
+            max_tokens_per_embedding_batch: self.max_tokens_per_embedding_batch,
+            embedding_cache_dir: self.embedding_cache_dir,
+            chunking: self.chunking,
+            element_type: self.element_type,
+            distance_metric: self.distance_metric,
+            index_config: self.index_config,
         })
     }
 }
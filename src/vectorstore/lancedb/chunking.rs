@@ -0,0 +1,180 @@
+use crate::schemas::Document;
+use serde_json::json;
+use uuid::Uuid;
+
+/// Bounds on how an incoming `Document` is split before embedding.
+#[derive(Clone, Copy)]
+pub(crate) struct ChunkConfig {
+    pub max_tokens: usize,
+    pub overlap_tokens: usize,
+}
+
+/// Rough characters-per-token ratio, matching the estimate used by the
+/// embedding queue's batching so the two stay in sync.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Splits `doc` into overlapping chunks bounded by `config.max_tokens`,
+/// each carrying the parent document's generated id and its **byte**
+/// offset range (`chunk_start`/`chunk_end`, end-exclusive) in metadata so
+/// a hit can be traced back to its span in the original document via
+/// `&doc.page_content[chunk_start..chunk_end]`. Chunk boundaries are
+/// chosen on char boundaries (so multi-byte UTF-8 is never split mid-
+/// character), but the recorded offsets are byte offsets, matching how
+/// Rust string slicing indexes. A document already within the budget
+/// comes back as a single "chunk" spanning the whole document.
+pub(crate) fn chunk_document(doc: &Document, config: &ChunkConfig) -> Vec<Document> {
+    let parent_id = Uuid::new_v4().to_string();
+    let max_chars = (config.max_tokens * CHARS_PER_TOKEN).max(1);
+    let overlap_chars = (config.overlap_tokens * CHARS_PER_TOKEN).min(max_chars.saturating_sub(1));
+
+    // Byte offset of each char, plus the content's total byte length so
+    // `boundaries[char_index]` is always valid, including `char_index ==
+    // total_chars`.
+    let mut boundaries: Vec<usize> = doc.page_content.char_indices().map(|(i, _)| i).collect();
+    boundaries.push(doc.page_content.len());
+    let total_chars = boundaries.len() - 1;
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    loop {
+        let end = (start + max_chars).min(total_chars);
+        let (start_byte, end_byte) = (boundaries[start], boundaries[end]);
+        chunks.push(make_chunk(
+            doc,
+            &doc.page_content[start_byte..end_byte],
+            &parent_id,
+            start_byte,
+            end_byte,
+        ));
+        if end == total_chars {
+            break;
+        }
+        start = end - overlap_chars;
+    }
+    chunks
+}
+
+fn make_chunk(
+    doc: &Document,
+    content: &str,
+    parent_id: &str,
+    start: usize,
+    end: usize,
+) -> Document {
+    let mut metadata = doc.metadata.clone();
+    metadata.insert("parent_id".to_string(), json!(parent_id));
+    metadata.insert("chunk_start".to_string(), json!(start));
+    metadata.insert("chunk_end".to_string(), json!(end));
+    Document {
+        page_content: content.to_string(),
+        metadata,
+        score: doc.score,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(content: &str) -> Document {
+        Document {
+            page_content: content.to_string(),
+            metadata: Default::default(),
+            score: 0.0,
+        }
+    }
+
+    #[test]
+    fn doc_within_budget_comes_back_as_a_single_chunk() {
+        let d = doc("short document");
+        let config = ChunkConfig {
+            max_tokens: 100,
+            overlap_tokens: 10,
+        };
+        let chunks = chunk_document(&d, &config);
+
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].page_content, "short document");
+        assert_eq!(chunks[0].metadata["chunk_start"], json!(0));
+        assert_eq!(chunks[0].metadata["chunk_end"], json!(d.page_content.len()));
+    }
+
+    #[test]
+    fn long_doc_splits_into_overlapping_chunks_with_shared_parent_id() {
+        // max_tokens=2 -> 8-char chunks, overlap_tokens=1 -> 4-char overlap.
+        let content: String = ('a'..='t').collect(); // 20 chars
+        let d = doc(&content);
+        let config = ChunkConfig {
+            max_tokens: 2,
+            overlap_tokens: 1,
+        };
+        let chunks = chunk_document(&d, &config);
+
+        let bounds: Vec<(i64, i64)> = chunks
+            .iter()
+            .map(|c| {
+                (
+                    c.metadata["chunk_start"].as_i64().unwrap(),
+                    c.metadata["chunk_end"].as_i64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(bounds, vec![(0, 8), (4, 12), (8, 16), (12, 20)]);
+
+        let parent_ids: std::collections::HashSet<_> = chunks
+            .iter()
+            .map(|c| c.metadata["parent_id"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(parent_ids.len(), 1, "all chunks share one parent id");
+
+        assert_eq!(chunks[0].page_content, &content[0..8]);
+        assert_eq!(chunks.last().unwrap().page_content, &content[12..20]);
+    }
+
+    #[test]
+    fn zero_overlap_produces_adjacent_chunks() {
+        let content: String = ('a'..='h').collect(); // 8 chars
+        let d = doc(&content);
+        let config = ChunkConfig {
+            max_tokens: 1,
+            overlap_tokens: 0,
+        };
+        let chunks = chunk_document(&d, &config);
+
+        let bounds: Vec<(i64, i64)> = chunks
+            .iter()
+            .map(|c| {
+                (
+                    c.metadata["chunk_start"].as_i64().unwrap(),
+                    c.metadata["chunk_end"].as_i64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(bounds, vec![(0, 4), (4, 8)]);
+    }
+
+    #[test]
+    fn multi_byte_content_yields_byte_offsets_safe_to_slice_with() {
+        // Each "é" is 2 bytes but 1 char, so char count (11) and byte count
+        // (19) diverge; offsets must still land on char boundaries.
+        let content = "éé éé éé éé";
+        let d = doc(content);
+        let config = ChunkConfig {
+            max_tokens: 1,
+            overlap_tokens: 0,
+        };
+        let chunks = chunk_document(&d, &config);
+
+        for chunk in &chunks {
+            let start = chunk.metadata["chunk_start"].as_i64().unwrap() as usize;
+            let end = chunk.metadata["chunk_end"].as_i64().unwrap() as usize;
+            // Panics if start/end aren't on a char boundary, and mismatched
+            // values would fail the assert either way.
+            assert_eq!(&content[start..end], chunk.page_content);
+        }
+        assert_eq!(
+            chunks.iter().map(|c| c.page_content.clone()).collect::<String>(),
+            content
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    error::Error,
+    hash::{Hash, Hasher},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::embedding::Embedder;
+
+/// Ceiling on retries against a rate-limited embedder before giving up.
+const DEFAULT_MAX_RETRIES: u32 = 5;
+
+/// Backoff delay before the first retry; doubles on each subsequent attempt
+/// unless the provider told us how long to wait.
+const DEFAULT_BASE_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Batches texts for embedding so a single provider call stays under a
+/// token budget, caches embeddings on disk keyed by content hash so
+/// unchanged documents are never re-embedded across runs, and retries
+/// rate-limited provider calls with exponential backoff.
+pub struct EmbeddingQueue {
+    embedder: Arc<dyn Embedder>,
+    embedder_id: String,
+    max_tokens_per_batch: usize,
+    cache_dir: Option<PathBuf>,
+}
+
+impl EmbeddingQueue {
+    pub fn new(
+        embedder: Arc<dyn Embedder>,
+        embedder_id: impl Into<String>,
+        max_tokens_per_batch: usize,
+    ) -> Self {
+        Self {
+            embedder,
+            embedder_id: embedder_id.into(),
+            max_tokens_per_batch,
+            cache_dir: None,
+        }
+    }
+
+    /// Enables the on-disk content-addressed cache, creating `cache_dir`
+    /// lazily on first write.
+    pub fn with_cache_dir(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(cache_dir.into());
+        self
+    }
+
+    /// Groups `pending` into batches whose estimated token count stays
+    /// under `max_tokens_per_batch`. A single text larger than the budget
+    /// still gets its own one-item batch rather than being dropped.
+    pub(crate) fn batch_by_tokens(&self, pending: &[(usize, String)]) -> Vec<Vec<(usize, String)>> {
+        let mut batches = Vec::new();
+        let mut current: Vec<(usize, String)> = Vec::new();
+        let mut current_tokens = 0;
+        for (index, text) in pending {
+            let tokens = Self::estimate_tokens(text);
+            if !current.is_empty() && current_tokens + tokens > self.max_tokens_per_batch {
+                batches.push(std::mem::take(&mut current));
+                current_tokens = 0;
+            }
+            current_tokens += tokens;
+            current.push((*index, text.clone()));
+        }
+        if !current.is_empty() {
+            batches.push(current);
+        }
+        batches
+    }
+
+    /// Embeds a single already-batched chunk of texts, retrying on a
+    /// rate-limit error with exponential backoff.
+    pub(crate) async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f64>>, Box<dyn Error>> {
+        let mut attempt = 0;
+        loop {
+            match self.embedder.embed_documents(texts).await {
+                Ok(embeddings) => return Ok(embeddings),
+                Err(error) if attempt < DEFAULT_MAX_RETRIES && Self::is_rate_limited(&error) => {
+                    tokio::time::sleep(Self::retry_delay(&error, attempt)).await;
+                    attempt += 1;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Rough token estimate (~4 characters per token) good enough for
+    /// batching decisions without pulling in a tokenizer dependency.
+    fn estimate_tokens(text: &str) -> usize {
+        (text.chars().count() / 4).max(1)
+    }
+
+    pub(crate) fn cache_key(&self, text: &str) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.embedder_id.hash(&mut hasher);
+        text.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+
+    pub(crate) fn read_cached(&self, key: &str) -> Option<Vec<f64>> {
+        let path = self.cache_dir.as_ref()?.join(format!("{key}.json"));
+        let bytes = std::fs::read(path).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    pub(crate) fn write_cached(&self, key: &str, embedding: &[f64]) -> Result<(), Box<dyn Error>> {
+        let Some(cache_dir) = self.cache_dir.as_ref() else {
+            return Ok(());
+        };
+        std::fs::create_dir_all(cache_dir)?;
+        std::fs::write(
+            cache_dir.join(format!("{key}.json")),
+            serde_json::to_vec(embedding)?,
+        )?;
+        Ok(())
+    }
+
+    /// Heuristically detects a rate-limit error from its message, since
+    /// `Embedder::embed_documents` returns an opaque `Box<dyn Error>`.
+    fn is_rate_limited(error: &dyn Error) -> bool {
+        let message = error.to_string().to_lowercase();
+        message.contains("429")
+            || message.contains("rate limit")
+            || message.contains("too many requests")
+    }
+
+    /// Honors a server-provided "retry after N seconds" hint embedded in
+    /// the error message when present, otherwise doubles
+    /// `DEFAULT_BASE_BACKOFF` per attempt.
+    fn retry_delay(error: &dyn Error, attempt: u32) -> Duration {
+        let message = error.to_string().to_lowercase();
+        if let Some(seconds) = Self::parse_retry_after_seconds(&message) {
+            return Duration::from_secs(seconds);
+        }
+        DEFAULT_BASE_BACKOFF * 2u32.pow(attempt)
+    }
+
+    fn parse_retry_after_seconds(message: &str) -> Option<u64> {
+        let marker = "retry after ";
+        let start = message.find(marker)? + marker.len();
+        let digits: String = message[start..]
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        digits.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+
+    struct NullEmbedder;
+
+    #[async_trait]
+    impl Embedder for NullEmbedder {
+        async fn embed_documents(
+            &self,
+            documents: &[String],
+        ) -> Result<Vec<Vec<f64>>, Box<dyn Error>> {
+            Ok(documents.iter().map(|_| vec![0.0]).collect())
+        }
+
+        async fn embed_query(&self, _text: &str) -> Result<Vec<f64>, Box<dyn Error>> {
+            Ok(vec![0.0])
+        }
+    }
+
+    fn queue(max_tokens_per_batch: usize) -> EmbeddingQueue {
+        EmbeddingQueue::new(Arc::new(NullEmbedder), "test", max_tokens_per_batch)
+    }
+
+    fn pending(texts: &[&str]) -> Vec<(usize, String)> {
+        texts
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (i, t.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn batches_stay_under_the_token_budget() {
+        // Each text is 4 chars -> 1 estimated token.
+        let texts = ["aaaa", "bbbb", "cccc", "dddd", "eeee"];
+        let batches = queue(2).batch_by_tokens(&pending(&texts));
+
+        let sizes: Vec<usize> = batches.iter().map(|b| b.len()).collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+    }
+
+    #[test]
+    fn oversized_single_text_gets_its_own_batch_instead_of_being_dropped() {
+        let huge = "x".repeat(40); // ~10 estimated tokens, over the budget
+        let batches = queue(2).batch_by_tokens(&pending(&[&huge]));
+
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].len(), 1);
+    }
+
+    #[test]
+    fn empty_pending_produces_no_batches() {
+        let batches = queue(2).batch_by_tokens(&[]);
+        assert!(batches.is_empty());
+    }
+
+    #[test]
+    fn parses_retry_after_hint_when_present() {
+        assert_eq!(
+            EmbeddingQueue::parse_retry_after_seconds("rate limited, retry after 42 seconds"),
+            Some(42)
+        );
+    }
+
+    #[test]
+    fn missing_retry_after_hint_returns_none() {
+        assert_eq!(
+            EmbeddingQueue::parse_retry_after_seconds("rate limited"),
+            None
+        );
+    }
+}
@@ -1,7 +1,10 @@
-use std::{error::Error, sync::Arc};
+use std::{collections::HashMap, error::Error, path::PathBuf, sync::Arc};
 
 use crate::{embedding::Embedder, schemas::Document, vectorstore::VectorStore};
-use arrow::datatypes::Float64Type;
+
+use super::chunking::{chunk_document, ChunkConfig};
+use super::embedding_queue::EmbeddingQueue;
+use arrow::datatypes::{Float32Type, Float64Type};
 use arrow_array::{
     FixedSizeListArray, Float32Array, RecordBatch, RecordBatchIterator, StringArray,
 };
@@ -10,7 +13,7 @@ use async_trait::async_trait;
 use futures::TryStreamExt;
 use lancedb::{
     arrow::arrow_schema::Schema,
-    index::Index,
+    index::{scalar::FtsIndexBuilder, vector::IvfPqIndexBuilder, Index},
     query::{ExecutableQuery, QueryBase},
     Connection, DistanceType,
 };
@@ -18,11 +21,64 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use uuid::Uuid;
 
+/// Default number of candidates pulled from each retriever before fusion,
+/// as a multiple of the requested `limit`.
+const DEFAULT_CANDIDATE_DEPTH_MULTIPLIER: usize = 4;
+
+/// Storage width for `text_embedding` values. `Float32` halves storage
+/// and memory versus `Float64` and is the default; `Float64` is kept for
+/// callers that already have f64-precision embeddings on disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VectorElementType {
+    Float32,
+    Float64,
+}
+
+/// Vector index configuration for the `text_embedding` column.
+#[derive(Clone, Copy, Debug)]
+pub enum IndexConfig {
+    /// Let LanceDB pick an index based on row count.
+    Auto,
+    /// An IVF_PQ index with explicit partition/sub-vector counts, trading
+    /// index build time and memory for recall/latency.
+    IvfPq {
+        num_partitions: u32,
+        num_sub_vectors: u32,
+    },
+}
+
 pub struct Store {
     pub(crate) connection: Connection,
     pub(crate) table: String,
     pub(crate) vector_dimensions: i32,
     pub(crate) embedder: Arc<dyn Embedder>,
+    pub(crate) rrf_k: usize,
+    pub(crate) hybrid_candidate_depth: Option<usize>,
+    /// `Document.metadata` keys that get their own scalar column so they
+    /// can be prefiltered on via `VecStoreOptions::filters`.
+    pub(crate) filterable_metadata_keys: Vec<String>,
+    /// Identifies the embedder in the on-disk embedding cache key; bump
+    /// this if the embedder's model changes so stale vectors aren't reused.
+    pub(crate) embedder_cache_id: String,
+    /// When set, `add_documents` routes embedding through an
+    /// [`EmbeddingQueue`] that batches by token budget, caches by content
+    /// hash, and retries rate-limited calls instead of embedding the
+    /// whole input slice in one shot.
+    pub(crate) max_tokens_per_embedding_batch: Option<usize>,
+    pub(crate) embedding_cache_dir: Option<PathBuf>,
+    /// When set, `add_documents` splits each incoming document into
+    /// overlapping chunks before embedding, one row per chunk.
+    pub(crate) chunking: Option<ChunkConfig>,
+    pub(crate) element_type: VectorElementType,
+    pub(crate) distance_metric: DistanceType,
+    pub(crate) index_config: IndexConfig,
+}
+
+/// A single retriever's hit, keyed by row id so results from different
+/// retrievers can be matched up and fused.
+struct RankedHit {
+    id: String,
+    document: Document,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -49,30 +105,51 @@ impl Store {
     }
 
     async fn create_table_if_not_exists(&self) -> Result<(), Box<dyn Error>> {
+        let mut fields = vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("text", DataType::Utf8, false),
+            Field::new("metadata", DataType::Utf8, false),
+        ];
+        for key in &self.filterable_metadata_keys {
+            fields.push(Field::new(
+                Self::filter_column_name(key),
+                DataType::Utf8,
+                true,
+            ));
+        }
+        let element_type = match self.element_type {
+            VectorElementType::Float32 => DataType::Float32,
+            VectorElementType::Float64 => DataType::Float64,
+        };
+        fields.push(Field::new(
+            "text_embedding",
+            DataType::FixedSizeList(
+                Arc::new(Field::new("vector", element_type, false)),
+                self.vector_dimensions,
+            ),
+            false,
+        ));
+
         let tb = self
             .connection
-            .create_empty_table(
-                &self.table,
-                Arc::new(Schema::new(vec![
-                    Field::new("id", DataType::Utf8, false),
-                    Field::new("text", DataType::Utf8, false),
-                    Field::new("metadata", DataType::Utf8, false),
-                    Field::new(
-                        "text_embedding",
-                        DataType::FixedSizeList(
-                            Arc::new(Field::new("vector", DataType::Float64, false)),
-                            self.vector_dimensions,
-                        ),
-                        false,
-                    ),
-                ])),
-            )
+            .create_empty_table(&self.table, Arc::new(Schema::new(fields)))
             .execute()
             .await;
         match tb {
             Ok(table) => {
+                // `Auto` adapts to however many rows exist (including zero)
+                // and is safe to build immediately. `IvfPq` needs training
+                // vectors to cluster, so it's deferred to
+                // `maybe_build_vector_index`, called once the table has
+                // data to train on.
+                if matches!(self.index_config, IndexConfig::Auto) {
+                    table
+                        .create_index(&["text_embedding"], Index::Auto)
+                        .execute()
+                        .await?;
+                }
                 table
-                    .create_index(&["text_embedding"], Index::Auto)
+                    .create_index(&["text"], Index::FTS(FtsIndexBuilder::default()))
                     .execute()
                     .await?
             }
@@ -88,6 +165,241 @@ impl Store {
         Ok(())
     }
 
+    /// Name of the scalar column a filterable metadata `key` is promoted to.
+    fn filter_column_name(key: &str) -> String {
+        format!("meta_{key}")
+    }
+
+    /// Renders a metadata value as the string stored in its filter column:
+    /// strings are stored as-is, everything else (numbers, bools) as their
+    /// JSON text so SQL predicates can compare against it directly.
+    fn metadata_value_to_filter_string(value: &serde_json::Value) -> Option<String> {
+        match value {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(s) => Some(s.clone()),
+            other => Some(other.to_string()),
+        }
+    }
+
+    /// Builds the `only_if` SQL prefilter from `VecStoreOptions::filters`.
+    /// A string filter is used as a raw SQL predicate; a JSON object is
+    /// turned into an `AND`-ed equality predicate over the document's
+    /// filterable metadata columns.
+    fn build_filter_predicate(&self, opt: &crate::vectorstore::VecStoreOptions) -> Option<String> {
+        Self::filter_predicate_from(&self.filterable_metadata_keys, opt.filters.as_ref()?)
+    }
+
+    /// Pure core of [`Self::build_filter_predicate`], taking the store's
+    /// filterable keys explicitly so it's testable without a live `Store`.
+    fn filter_predicate_from(
+        filterable_keys: &[String],
+        filters: &serde_json::Value,
+    ) -> Option<String> {
+        match filters {
+            serde_json::Value::String(raw_predicate) => Some(raw_predicate.clone()),
+            serde_json::Value::Object(map) => {
+                let clauses: Vec<String> = map
+                    .iter()
+                    .filter(|(key, _)| filterable_keys.iter().any(|k| k == *key))
+                    .filter_map(|(key, value)| {
+                        let value = Self::metadata_value_to_filter_string(value)?;
+                        Some(format!(
+                            "{} = '{}'",
+                            Self::filter_column_name(key),
+                            value.replace('\'', "''")
+                        ))
+                    })
+                    .collect();
+                if clauses.is_empty() {
+                    None
+                } else {
+                    Some(clauses.join(" AND "))
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// For a `Cosine`/`Dot` distance metric, scales `vector` to unit length
+    /// so a plain dot product at query time yields cosine similarity.
+    /// `L2` vectors are left untouched.
+    fn normalize_if_needed(&self, mut vector: Vec<f64>) -> Vec<f64> {
+        if matches!(self.distance_metric, DistanceType::Cosine | DistanceType::Dot) {
+            let norm = vector.iter().map(|x| x * x).sum::<f64>().sqrt();
+            if norm > 0.0 {
+                for x in vector.iter_mut() {
+                    *x /= norm;
+                }
+            }
+        }
+        vector
+    }
+
+    /// Builds the `text_embedding` column in the element type configured
+    /// on the store (`f32` by default, `f64` opt-in).
+    fn build_embedding_column(&self, embeddings: &[Vec<f64>]) -> Arc<dyn arrow_array::Array> {
+        match self.element_type {
+            VectorElementType::Float32 => {
+                Arc::new(FixedSizeListArray::from_iter_primitive::<Float32Type, _, _>(
+                    embeddings
+                        .iter()
+                        .map(|v| v.iter().map(|x| Some(*x as f32)).collect::<Vec<_>>())
+                        .map(Some),
+                    self.vector_dimensions,
+                ))
+            }
+            VectorElementType::Float64 => {
+                Arc::new(FixedSizeListArray::from_iter_primitive::<Float64Type, _, _>(
+                    embeddings
+                        .iter()
+                        .map(|v| v.iter().map(|x| Some(*x)).collect::<Vec<_>>())
+                        .map(Some),
+                    self.vector_dimensions,
+                ))
+            }
+        }
+    }
+
+    /// Parses the `id`/`text`/`metadata`/`_distance` columns out of a query's
+    /// result batches, in the order LanceDB returned them (i.e. already
+    /// ranked best-first).
+    fn parse_ranked_hits(results: Vec<RecordBatch>) -> Vec<RankedHit> {
+        let mut hits = Vec::new();
+        for result in results {
+            let len = result.num_rows();
+            let ids = result
+                .column_by_name("id")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap();
+            let contents = result
+                .column_by_name("text")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap();
+            let metadatas = result
+                .column_by_name("metadata")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap();
+            let scores = result
+                .column_by_name("_distance")
+                .and_then(|c| c.as_any().downcast_ref::<Float32Array>().cloned());
+            for i in 0..len {
+                hits.push(RankedHit {
+                    id: ids.value(i).into(),
+                    document: Document {
+                        page_content: contents.value(i).into(),
+                        metadata: serde_json::from_str(metadatas.value(i)).unwrap(),
+                        score: scores.as_ref().map(|s| s.value(i).into()).unwrap_or(0.0),
+                    },
+                });
+            }
+        }
+        hits
+    }
+
+    /// Runs the pure vector retriever, scored with the store's configured
+    /// distance metric, and returns its hits in
+    /// rank order, best match first. `predicate`, when set, is applied as
+    /// an `only_if` SQL prefilter before the nearest-neighbor scan.
+    async fn vector_search_ranked(
+        &self,
+        query: &str,
+        limit: usize,
+        predicate: Option<&str>,
+    ) -> Result<Vec<RankedHit>, Box<dyn Error>> {
+        let query_vector = self.normalize_if_needed(self.embedder.embed_query(query).await?);
+        let table = self.connection.open_table(&self.table).execute().await?;
+        let mut query = table
+            .query()
+            .nearest_to(query_vector)?
+            .column("text_embedding")
+            .distance_type(self.distance_metric)
+            .limit(limit);
+        if let Some(predicate) = predicate {
+            query = query.only_if(predicate);
+        }
+        let results = query.execute().await?.try_collect::<Vec<_>>().await?;
+        Ok(Self::parse_ranked_hits(results))
+    }
+
+    /// Runs the full-text (BM25) retriever over the `text` column and
+    /// returns its hits in rank order, best match first. `predicate`, when
+    /// set, is applied as an `only_if` SQL prefilter.
+    async fn keyword_search_ranked(
+        &self,
+        query: &str,
+        limit: usize,
+        predicate: Option<&str>,
+    ) -> Result<Vec<RankedHit>, Box<dyn Error>> {
+        let table = self.connection.open_table(&self.table).execute().await?;
+        let mut query = table
+            .query()
+            .full_text_search(lancedb::query::FullTextSearchQuery::new(query.to_owned()))
+            .limit(limit);
+        if let Some(predicate) = predicate {
+            query = query.only_if(predicate);
+        }
+        let results = query.execute().await?.try_collect::<Vec<_>>().await?;
+        Ok(Self::parse_ranked_hits(results))
+    }
+
+    /// Fuses multiple rank-ordered hit lists with Reciprocal Rank Fusion:
+    /// `score = Σ 1 / (k + rank_i)` over the lists a document appears in
+    /// (rank starting at 1). Returns documents sorted by descending fused
+    /// score, with `Document.score` set to that fused score.
+    fn reciprocal_rank_fusion(lists: Vec<Vec<RankedHit>>, k: usize) -> Vec<Document> {
+        let mut scores: HashMap<String, f64> = HashMap::new();
+        let mut documents: HashMap<String, Document> = HashMap::new();
+        for list in lists {
+            for (rank, hit) in list.into_iter().enumerate() {
+                let contribution = 1.0 / (k + rank + 1) as f64;
+                *scores.entry(hit.id.clone()).or_insert(0.0) += contribution;
+                documents.entry(hit.id).or_insert(hit.document);
+            }
+        }
+        let mut fused: Vec<(String, f64)> = scores.into_iter().collect();
+        fused.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        fused
+            .into_iter()
+            .filter_map(|(id, score)| {
+                documents.remove(&id).map(|mut document| {
+                    document.score = score;
+                    document
+                })
+            })
+            .collect()
+    }
+
+    /// Hybrid search: runs the vector and keyword retrievers independently,
+    /// each pulling `hybrid_candidate_depth` candidates (defaulting to
+    /// `4 * limit`), then fuses them with Reciprocal Rank Fusion and
+    /// returns the top `limit` documents. `Document.score` carries the
+    /// fused RRF score so callers can threshold on it.
+    pub async fn similarity_search_hybrid(
+        &self,
+        query: &str,
+        limit: usize,
+        opt: &crate::vectorstore::VecStoreOptions,
+    ) -> Result<Vec<Document>, Box<dyn Error>> {
+        let candidate_depth = self
+            .hybrid_candidate_depth
+            .unwrap_or(limit * DEFAULT_CANDIDATE_DEPTH_MULTIPLIER);
+        let predicate = self.build_filter_predicate(opt);
+
+        let (vector_hits, keyword_hits) = futures::try_join!(
+            self.vector_search_ranked(query, candidate_depth, predicate.as_deref()),
+            self.keyword_search_ranked(query, candidate_depth, predicate.as_deref())
+        )?;
+
+        let fused = Self::reciprocal_rank_fusion(vec![vector_hits, keyword_hits], self.rrf_k);
+        Ok(fused.into_iter().take(limit).collect())
+    }
+
     async fn drop_table(&self) -> Result<(), Box<dyn Error>> {
         let tables: Vec<String> = self
             .connection
@@ -96,13 +408,181 @@ impl Store {
             .await?
             .into_iter()
             .collect();
-        if !tables.contains(&self.table) {
+        if tables.contains(&self.table) {
             if let Err(error) = self.connection.drop_table(&self.table).await {
                 return Err::<(), Box<dyn Error>>(Box::new(error));
             }
         }
         Ok(())
     }
+
+    /// Deletes the rows whose generated `id` is in `ids`. A no-op for an
+    /// empty `ids`.
+    pub async fn delete_documents(&self, ids: &[String]) -> Result<(), Box<dyn Error>> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let table = self.connection.open_table(&self.table).execute().await?;
+        table
+            .delete(&format!("id IN ({})", Self::quoted_csv(ids)))
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes any existing rows whose filterable metadata column `key`
+    /// matches one of `values`, ahead of an upsert's insert. A no-op if
+    /// `values` is empty. Errors if `key` isn't a configured filterable
+    /// metadata key, since silently skipping the delete would let upserts
+    /// insert duplicate rows with no indication why.
+    async fn delete_by_metadata_key(
+        &self,
+        table: &lancedb::Table,
+        key: &str,
+        values: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        Self::require_filterable_key(&self.filterable_metadata_keys, key)?;
+        if values.is_empty() {
+            return Ok(());
+        }
+        let predicate = format!(
+            "{} IN ({})",
+            Self::filter_column_name(key),
+            Self::quoted_csv(values)
+        );
+        table.delete(&predicate).await?;
+        Ok(())
+    }
+
+    /// Errors unless `key` is registered in `filterable_keys`, since
+    /// silently skipping the delete on an unregistered upsert key would
+    /// let upserts insert duplicate rows with no indication why.
+    fn require_filterable_key(filterable_keys: &[String], key: &str) -> Result<(), Box<dyn Error>> {
+        if filterable_keys.iter().any(|k| k == key) {
+            Ok(())
+        } else {
+            Err(format!(
+                "upsert_key `{key}` must also be registered via \
+                 StoreBuilder::filterable_metadata_keys to be used for upserts"
+            )
+            .into())
+        }
+    }
+
+    fn quoted_csv<S: AsRef<str>>(values: &[S]) -> String {
+        values
+            .iter()
+            .map(|v| format!("'{}'", v.as_ref().replace('\'', "''")))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Builds `VectorRecord`s for `docs[indices]` (with their resolved
+    /// `embeddings`) and writes them to the table in a single `add` call,
+    /// so a batch either lands whole or not at all.
+    async fn write_batch(
+        &self,
+        table: &lancedb::Table,
+        schema: &Arc<Schema>,
+        docs: &[Document],
+        indices: &[usize],
+        embeddings: &[Option<Vec<f64>>],
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let vector_records: Vec<VectorRecord> = indices
+            .iter()
+            .map(|&i| VectorRecord {
+                id: Uuid::new_v4().to_string(),
+                text: docs[i].page_content.clone(),
+                metadata: json!(docs[i].metadata).to_string(),
+                text_embedding: self.normalize_if_needed(
+                    embeddings[i]
+                        .clone()
+                        .expect("embedding resolved before write_batch is called"),
+                ),
+            })
+            .collect();
+
+        let mut columns: Vec<Arc<dyn arrow_array::Array>> = vec![
+            Arc::new(StringArray::from_iter_values(
+                vector_records.iter().map(|d| d.id.clone()),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                vector_records.iter().map(|d| d.text.clone()),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                vector_records.iter().map(|d| d.metadata.clone()),
+            )),
+        ];
+        for key in &self.filterable_metadata_keys {
+            columns.push(Arc::new(StringArray::from_iter(indices.iter().map(
+                |&i| {
+                    docs[i]
+                        .metadata
+                        .get(key)
+                        .and_then(Self::metadata_value_to_filter_string)
+                },
+            ))));
+        }
+        let embeddings: Vec<Vec<f64>> = vector_records
+            .iter()
+            .map(|d| d.text_embedding.clone())
+            .collect();
+        columns.push(self.build_embedding_column(&embeddings));
+
+        let batches = RecordBatchIterator::new(
+            vec![RecordBatch::try_new(schema.clone(), columns).unwrap()]
+                .into_iter()
+                .map(Ok),
+            schema.clone(),
+        );
+
+        let ids: Vec<String> = vector_records.iter().map(|v| v.id.clone()).collect();
+        table.add(batches).execute().await?;
+        self.maybe_build_vector_index(table).await?;
+        Ok(ids)
+    }
+
+    /// Builds the configured `IvfPq` vector index once the table has at
+    /// least `num_partitions` rows to train on; a no-op for `IndexConfig::
+    /// Auto`, which is already built in `create_table_if_not_exists`. Also
+    /// a no-op once the index already exists, or while the table still has
+    /// too few rows to train the clusters, so calling this after every
+    /// batch write only ever builds the index once — the first batch that
+    /// crosses the row threshold — instead of rebuilding it from scratch
+    /// on every subsequent insert.
+    async fn maybe_build_vector_index(&self, table: &lancedb::Table) -> Result<(), Box<dyn Error>> {
+        let IndexConfig::IvfPq {
+            num_partitions,
+            num_sub_vectors,
+        } = self.index_config
+        else {
+            return Ok(());
+        };
+        let already_indexed = table
+            .list_indices()
+            .await?
+            .iter()
+            .any(|index| index.columns.iter().any(|c| c == "text_embedding"));
+        if already_indexed {
+            return Ok(());
+        }
+        let row_count = table.count_rows(None).await?;
+        if row_count < num_partitions as usize {
+            return Ok(());
+        }
+        table
+            .create_index(
+                &["text_embedding"],
+                Index::IvfPq(
+                    IvfPqIndexBuilder::default()
+                        .num_partitions(num_partitions)
+                        .num_sub_vectors(num_sub_vectors)
+                        .distance_type(self.distance_metric),
+                ),
+            )
+            .execute()
+            .await?;
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -112,128 +592,258 @@ impl VectorStore for Store {
         docs: &[crate::schemas::Document],
         opt: &crate::vectorstore::VecStoreOptions,
     ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-        let texts: Vec<String> = docs.iter().map(|d| d.page_content.clone()).collect();
+        let chunked_docs;
+        let docs: &[Document] = match &self.chunking {
+            Some(config) => {
+                chunked_docs = docs
+                    .iter()
+                    .flat_map(|d| chunk_document(d, config))
+                    .collect::<Vec<_>>();
+                &chunked_docs
+            }
+            None => docs,
+        };
 
-        let embedder = opt.embedder.as_ref().unwrap_or(&self.embedder);
+        let texts: Vec<String> = docs.iter().map(|d| d.page_content.clone()).collect();
+        let embedder = opt
+            .embedder
+            .clone()
+            .unwrap_or_else(|| self.embedder.clone());
 
-        let vectors = embedder.embed_documents(&texts).await?;
-        if vectors.len() != docs.len() {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Number of vectors and documents do not match",
-            )));
-        }
         let table = self.connection.open_table(&self.table).execute().await?;
         let schema = table.schema().await?;
 
-        let vector_records: Vec<VectorRecord> = docs
-            .iter()
-            .zip(vectors)
-            .map(|(d, v)| VectorRecord {
-                id: Uuid::new_v4().to_string(),
-                text: d.page_content.clone(),
-                metadata: json!(d.metadata).to_string(),
-                text_embedding: v,
-            })
-            .collect();
+        if let Some(upsert_key) = &opt.upsert_key {
+            let mut stable_ids: Vec<String> = docs
+                .iter()
+                .filter_map(|d| d.metadata.get(upsert_key))
+                .filter_map(Self::metadata_value_to_filter_string)
+                .collect();
+            stable_ids.sort();
+            stable_ids.dedup();
+            self.delete_by_metadata_key(&table, upsert_key, &stable_ids)
+                .await?;
+        }
 
-        let table = &self.table;
-        let tb = self.connection.open_table(table).execute().await?;
+        let Some(max_tokens_per_batch) = self.max_tokens_per_embedding_batch else {
+            let vectors = embedder.embed_documents(&texts).await?;
+            if vectors.len() != docs.len() {
+                return Err(Box::new(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "Number of vectors and documents do not match",
+                )));
+            }
+            let embeddings: Vec<Option<Vec<f64>>> = vectors.into_iter().map(Some).collect();
+            let indices: Vec<usize> = (0..docs.len()).collect();
+            return self
+                .write_batch(&table, &schema, docs, &indices, &embeddings)
+                .await;
+        };
 
-        let batches = RecordBatchIterator::new(
-            vec![RecordBatch::try_new(
-                schema.clone(),
-                vec![
-                    Arc::new(StringArray::from_iter_values(
-                        vector_records.iter().map(|d| d.id.clone()),
-                    )),
-                    Arc::new(StringArray::from_iter_values(
-                        vector_records.iter().map(|d| d.text.clone()),
-                    )),
-                    Arc::new(StringArray::from_iter_values(
-                        vector_records.iter().map(|d| d.metadata.clone()),
-                    )),
-                    Arc::new(
-                        FixedSizeListArray::from_iter_primitive::<Float64Type, _, _>(
-                            vector_records
-                                .iter()
-                                .map(|d| {
-                                    d.text_embedding
-                                        .clone()
-                                        .into_iter()
-                                        .map(Some)
-                                        .collect::<Vec<Option<f64>>>()
-                                })
-                                .map(Some),
-                            self.vector_dimensions,
-                        ),
-                    ),
-                ],
-            )
-            .unwrap()]
-            .into_iter()
-            .map(Ok),
-            schema.clone(),
+        // Batched path: check the content-addressed cache first, then embed
+        // only the uncached texts in token-budgeted batches, flushing each
+        // batch's records as soon as it is embedded so a mid-ingest failure
+        // leaves the already-flushed batches intact.
+        let queue = EmbeddingQueue::new(
+            embedder,
+            self.embedder_cache_id.clone(),
+            max_tokens_per_batch,
         );
+        let queue = match &self.embedding_cache_dir {
+            Some(dir) => queue.with_cache_dir(dir.clone()),
+            None => queue,
+        };
+
+        let keys: Vec<String> = texts.iter().map(|t| queue.cache_key(t)).collect();
+        let mut resolved: Vec<Option<Vec<f64>>> =
+            keys.iter().map(|k| queue.read_cached(k)).collect();
+
+        // `write_batch` returns ids in the same order as the `group`
+        // indices it was given, but the groups (cached group first, then
+        // each provider batch) are not in input order, so ids are
+        // scattered back into their original positions rather than just
+        // appended, keeping `ids[i] <-> docs[i]`.
+        let mut ids: Vec<Option<String>> = vec![None; docs.len()];
+        let cached_group: Vec<usize> = resolved
+            .iter()
+            .enumerate()
+            .filter(|(_, embedding)| embedding.is_some())
+            .map(|(i, _)| i)
+            .collect();
+        if !cached_group.is_empty() {
+            let group_ids = self
+                .write_batch(&table, &schema, docs, &cached_group, &resolved)
+                .await?;
+            for (index, id) in cached_group.into_iter().zip(group_ids) {
+                ids[index] = Some(id);
+            }
+        }
 
-        let ids: Vec<String> = vector_records.into_iter().map(|v| v.id.clone()).collect();
+        let pending: Vec<(usize, String)> = resolved
+            .iter()
+            .enumerate()
+            .filter(|(_, embedding)| embedding.is_none())
+            .map(|(i, _)| (i, texts[i].clone()))
+            .collect();
 
-        match tb.add(batches).execute().await {
-            Ok(_) => Ok(ids),
-            Err(error) => Err(Box::new(error)),
+        for batch in queue.batch_by_tokens(&pending) {
+            let batch_texts: Vec<String> = batch.iter().map(|(_, text)| text.clone()).collect();
+            let batch_embeddings = queue.embed_batch(&batch_texts).await?;
+            for ((index, _), embedding) in batch.iter().zip(batch_embeddings) {
+                queue.write_cached(&keys[*index], &embedding)?;
+                resolved[*index] = Some(embedding);
+            }
+            let group: Vec<usize> = batch.into_iter().map(|(index, _)| index).collect();
+            let group_ids = self
+                .write_batch(&table, &schema, docs, &group, &resolved)
+                .await?;
+            for (index, id) in group.into_iter().zip(group_ids) {
+                ids[index] = Some(id);
+            }
         }
+        Ok(ids
+            .into_iter()
+            .map(|id| id.expect("every document index is written exactly once"))
+            .collect())
     }
 
     async fn similarity_search(
         &self,
         query: &str,
         limit: usize,
-        _opt: &crate::vectorstore::VecStoreOptions,
+        opt: &crate::vectorstore::VecStoreOptions,
     ) -> Result<Vec<crate::schemas::Document>, Box<dyn std::error::Error>> {
-        let query_vector = self.embedder.embed_query(query).await?;
-        let table = self.connection.open_table(&self.table).execute().await?;
-        let results = table
-            .query()
-            .nearest_to(query_vector)
-            .unwrap()
-            .column("text_embedding")
-            .distance_type(DistanceType::Cosine)
-            .limit(limit)
-            .execute()
-            .await
-            .unwrap()
-            .try_collect::<Vec<_>>()
-            .await
-            .unwrap();
-        let mut items: Vec<Document> = Vec::with_capacity(results.len());
-        for result in results {
-            let len = result.num_rows();
-            let contents = result
-                .column_by_name("text")
-                .unwrap()
-                .as_any()
-                .downcast_ref::<StringArray>()
-                .unwrap();
-            let metadatas = result
-                .column_by_name("metadata")
-                .unwrap()
-                .as_any()
-                .downcast_ref::<StringArray>()
-                .unwrap();
-            let scores = result
-                .column_by_name("_distance")
-                .unwrap()
-                .as_any()
-                .downcast_ref::<Float32Array>()
-                .unwrap();
-            for i in 0..len {
-                items.push(Document {
-                    page_content: contents.value(i).into(),
-                    metadata: serde_json::from_str(metadatas.value(i)).unwrap(),
-                    score: scores.value(i).into(),
-                })
-            }
+        let predicate = self.build_filter_predicate(opt);
+        let hits = self
+            .vector_search_ranked(query, limit, predicate.as_deref())
+            .await?;
+        Ok(hits.into_iter().map(|hit| hit.document).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(id: &str) -> RankedHit {
+        RankedHit {
+            id: id.to_string(),
+            document: Document {
+                page_content: id.to_string(),
+                metadata: Default::default(),
+                score: 0.0,
+            },
         }
-        Ok(items)
+    }
+
+    #[test]
+    fn fuses_and_ranks_by_combined_reciprocal_rank() {
+        // "a" is top of both lists and should win; "c" only appears in the
+        // second list and should still get a (smaller) contribution.
+        let vector_hits = vec![hit("a"), hit("b")];
+        let keyword_hits = vec![hit("a"), hit("c")];
+
+        let fused = Store::reciprocal_rank_fusion(vec![vector_hits, keyword_hits], 60);
+
+        let ids: Vec<&str> = fused.iter().map(|d| d.page_content.as_str()).collect();
+        assert_eq!(ids[0], "a");
+        assert_eq!(ids.len(), 3);
+
+        let score_of = |id: &str| fused.iter().find(|d| d.page_content == id).unwrap().score;
+        assert!((score_of("a") - (1.0 / 61.0 + 1.0 / 61.0)).abs() < 1e-9);
+        assert!((score_of("b") - (1.0 / 62.0)).abs() < 1e-9);
+        assert!((score_of("c") - (1.0 / 62.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn single_list_passes_through_in_rank_order() {
+        let fused = Store::reciprocal_rank_fusion(vec![vec![hit("a"), hit("b")]], 60);
+        let ids: Vec<&str> = fused.iter().map(|d| d.page_content.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn empty_lists_fuse_to_no_results() {
+        let fused = Store::reciprocal_rank_fusion(vec![vec![], vec![]], 60);
+        assert!(fused.is_empty());
+    }
+
+    #[test]
+    fn metadata_value_to_filter_string_renders_each_json_type() {
+        assert_eq!(
+            Store::metadata_value_to_filter_string(&serde_json::json!("docs")),
+            Some("docs".to_string())
+        );
+        assert_eq!(
+            Store::metadata_value_to_filter_string(&serde_json::json!(42)),
+            Some("42".to_string())
+        );
+        assert_eq!(
+            Store::metadata_value_to_filter_string(&serde_json::json!(true)),
+            Some("true".to_string())
+        );
+        assert_eq!(
+            Store::metadata_value_to_filter_string(&serde_json::Value::Null),
+            None
+        );
+    }
+
+    #[test]
+    fn string_filter_is_used_as_a_raw_predicate() {
+        let filters = serde_json::json!("source = 'docs'");
+        assert_eq!(
+            Store::filter_predicate_from(&[], &filters),
+            Some("source = 'docs'".to_string())
+        );
+    }
+
+    #[test]
+    fn object_filter_builds_anded_equality_predicate_and_escapes_quotes() {
+        let filterable_keys = vec!["source".to_string(), "tenant".to_string()];
+        let filters = serde_json::json!({"source": "o'brien", "tenant": "acme"});
+
+        let predicate = Store::filter_predicate_from(&filterable_keys, &filters).unwrap();
+
+        // Clause order follows JSON map iteration; check membership instead
+        // of exact string equality.
+        assert!(predicate.contains("meta_source = 'o''brien'"));
+        assert!(predicate.contains("meta_tenant = 'acme'"));
+        assert!(predicate.contains(" AND "));
+    }
+
+    #[test]
+    fn object_filter_drops_keys_not_registered_as_filterable() {
+        let filterable_keys = vec!["source".to_string()];
+        let filters = serde_json::json!({"source": "docs", "unregistered": "x"});
+
+        let predicate = Store::filter_predicate_from(&filterable_keys, &filters).unwrap();
+        assert_eq!(predicate, "meta_source = 'docs'");
+    }
+
+    #[test]
+    fn object_filter_with_no_filterable_keys_present_yields_no_predicate() {
+        let filters = serde_json::json!({"unregistered": "x"});
+        assert_eq!(Store::filter_predicate_from(&[], &filters), None);
+    }
+
+    #[test]
+    fn quoted_csv_joins_and_escapes_values() {
+        assert_eq!(Store::quoted_csv(&["a", "b"]), "'a', 'b'");
+        assert_eq!(Store::quoted_csv(&["o'brien"]), "'o''brien'");
+        assert_eq!(Store::quoted_csv::<&str>(&[]), "");
+    }
+
+    #[test]
+    fn require_filterable_key_passes_for_a_registered_key() {
+        let filterable_keys = vec!["source".to_string()];
+        assert!(Store::require_filterable_key(&filterable_keys, "source").is_ok());
+    }
+
+    #[test]
+    fn require_filterable_key_errors_for_an_unregistered_key() {
+        let filterable_keys = vec!["source".to_string()];
+        let error = Store::require_filterable_key(&filterable_keys, "doc_id").unwrap_err();
+        assert!(error.to_string().contains("doc_id"));
+        assert!(error.to_string().contains("filterable_metadata_keys"));
     }
 }
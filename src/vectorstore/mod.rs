@@ -0,0 +1,21 @@
+pub mod lancedb;
+
+use std::sync::Arc;
+
+use crate::embedding::Embedder;
+
+/// Per-call knobs for `VectorStore::add_documents`/`similarity_search`.
+#[derive(Clone, Default)]
+pub struct VecStoreOptions {
+    /// Metadata filter applied as a LanceDB SQL prefilter: a raw SQL string,
+    /// or a JSON object of equality constraints over filterable metadata
+    /// columns.
+    pub filters: Option<serde_json::Value>,
+    /// Overrides the store's default embedder for this call.
+    pub embedder: Option<Arc<dyn Embedder>>,
+    /// Opts `add_documents` into upsert mode: before inserting, deletes any
+    /// existing rows whose metadata at this key matches one of the
+    /// incoming documents' values for it. The key must also be registered
+    /// via `StoreBuilder::filterable_metadata_keys`.
+    pub upsert_key: Option<String>,
+}